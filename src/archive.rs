@@ -0,0 +1,106 @@
+//! Streaming reader for tar archives.
+//!
+//! [`Archive`] wraps any [`Read`] and hands out [`Entry`] values through its
+//! [`entries`](Archive::entries) iterator. Each `Entry` exposes its parsed
+//! [`Header`] and implements `Read` over exactly `header.size` bytes of the
+//! underlying stream, so a member of any size can be processed with constant
+//! memory via [`std::io::copy`]. The trailing 512-byte block padding is skipped
+//! automatically when an entry is dropped or fully consumed.
+
+use std::io::{self, Read};
+
+use anyhow::Result;
+
+use crate::header::Header;
+use crate::{DIRTYPE, LNKTYPE, SYMTYPE};
+
+/// A tar archive being read from an arbitrary stream.
+pub struct Archive<R: Read> {
+  reader: R,
+}
+
+impl<R: Read> Archive<R> {
+  pub fn new(reader: R) -> Self {
+    Archive { reader }
+  }
+
+  /// Iterate over the archive's entries. This is a lending iterator: each
+  /// [`Entry`] borrows the archive, so the current entry must be dropped
+  /// before the next one is requested.
+  pub fn entries(&mut self) -> Entries<'_, R> {
+    Entries {
+      reader: &mut self.reader,
+    }
+  }
+
+  /// Recover the underlying reader.
+  pub fn into_inner(self) -> R {
+    self.reader
+  }
+}
+
+/// Iterator over the entries of an [`Archive`].
+pub struct Entries<'a, R: Read> {
+  reader: &'a mut R,
+}
+
+impl<'a, R: Read> Entries<'a, R> {
+  /// Read the next entry, or `None` at the end of the archive.
+  #[allow(clippy::should_implement_trait)]
+  pub fn next(&mut self) -> Option<Result<Entry<'_, R>>> {
+    match Header::read(&mut *self.reader) {
+      Ok(Some(header)) => {
+        // Directories and links carry no content (and therefore no padding);
+        // everything else is a stream of `size` bytes padded to a 512-byte
+        // boundary.
+        let has_content =
+          !matches!(header.typeflag, DIRTYPE | SYMTYPE | LNKTYPE);
+        let (remaining, padding) = if has_content {
+          (header.size, (512 - (header.size % 512)) % 512)
+        } else {
+          (0, 0)
+        };
+
+        Some(Ok(Entry {
+          header,
+          reader: &mut *self.reader,
+          remaining,
+          padding,
+        }))
+      }
+      Ok(None) => None,
+      Err(e) => Some(Err(e)),
+    }
+  }
+}
+
+/// A single archive member. Reading it yields exactly the member's content;
+/// dropping it skips any unread content plus the trailing padding.
+pub struct Entry<'a, R: Read> {
+  pub header: Header,
+  reader: &'a mut R,
+  remaining: u64,
+  padding: u64,
+}
+
+impl<'a, R: Read> Read for Entry<'a, R> {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    if self.remaining == 0 {
+      return Ok(0);
+    }
+
+    let max = buf.len().min(self.remaining as usize);
+    let n = self.reader.read(&mut buf[..max])?;
+    self.remaining -= n as u64;
+    Ok(n)
+  }
+}
+
+impl<'a, R: Read> Drop for Entry<'a, R> {
+  fn drop(&mut self) {
+    // Discard any content the caller didn't read, plus the block padding, so
+    // the stream is positioned at the next header.
+    let skip = self.remaining + self.padding;
+    let _ = io::copy(&mut self.reader.by_ref().take(skip), &mut io::sink());
+  }
+}
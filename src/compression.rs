@@ -0,0 +1,147 @@
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use anyhow::Result;
+
+/// A compression codec wrapped transparently around the raw tar stream. Each
+/// codec other than `None` lives behind a cargo feature, mirroring nod-rs'
+/// `compress-zstd`/`compress-bzip2`/`compress-lzma` split.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+  None,
+  Gzip,
+  Zstd,
+  Bzip2,
+  Xz,
+}
+
+impl Compression {
+  /// Guess the codec from an archive file name's extension.
+  pub fn from_path(path: &str) -> Compression {
+    let lower = path.to_ascii_lowercase();
+    if lower.ends_with(".gz") || lower.ends_with(".tgz") {
+      Compression::Gzip
+    } else if lower.ends_with(".zst") || lower.ends_with(".tzst") {
+      Compression::Zstd
+    } else if lower.ends_with(".bz2") || lower.ends_with(".tbz2") {
+      Compression::Bzip2
+    } else if lower.ends_with(".xz") || lower.ends_with(".txz") {
+      Compression::Xz
+    } else {
+      Compression::None
+    }
+  }
+
+  /// Identify the codec from the leading magic bytes of a stream.
+  pub fn from_magic(bytes: &[u8]) -> Compression {
+    if bytes.starts_with(&[0x1f, 0x8b]) {
+      Compression::Gzip
+    } else if bytes.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+      Compression::Zstd
+    } else if bytes.starts_with(&[0xfd, 0x37, 0x7a, 0x58, 0x5a]) {
+      Compression::Xz
+    } else if bytes.starts_with(&[0x42, 0x5a, 0x68]) {
+      Compression::Bzip2
+    } else {
+      Compression::None
+    }
+  }
+
+  /// Sniff the codec from the first few bytes of a seekable stream, rewinding
+  /// back to the start afterwards.
+  pub fn detect<R: Read + Seek>(r: &mut R) -> Result<Compression> {
+    let mut magic = [0u8; 6];
+    let mut read = 0;
+    while read < magic.len() {
+      match r.read(&mut magic[read..])? {
+        0 => break,
+        n => read += n,
+      }
+    }
+    r.seek(SeekFrom::Start(0))?;
+    Ok(Compression::from_magic(&magic[..read]))
+  }
+
+  /// Wrap a writer in the appropriate encoder.
+  pub fn wrap_writer<W: Write + 'static>(
+    self,
+    w: W,
+  ) -> Result<Box<dyn Write>> {
+    match self {
+      Compression::None => Ok(Box::new(w)),
+
+      #[cfg(feature = "compress-gzip")]
+      Compression::Gzip => Ok(Box::new(flate2::write::GzEncoder::new(
+        w,
+        flate2::Compression::default(),
+      ))),
+      #[cfg(not(feature = "compress-gzip"))]
+      Compression::Gzip => {
+        bail!("gzip support not compiled in (enable `compress-gzip`)")
+      }
+
+      #[cfg(feature = "compress-zstd")]
+      Compression::Zstd => {
+        Ok(Box::new(zstd::stream::write::Encoder::new(w, 0)?.auto_finish()))
+      }
+      #[cfg(not(feature = "compress-zstd"))]
+      Compression::Zstd => {
+        bail!("zstd support not compiled in (enable `compress-zstd`)")
+      }
+
+      #[cfg(feature = "compress-bzip2")]
+      Compression::Bzip2 => Ok(Box::new(bzip2::write::BzEncoder::new(
+        w,
+        bzip2::Compression::default(),
+      ))),
+      #[cfg(not(feature = "compress-bzip2"))]
+      Compression::Bzip2 => {
+        bail!("bzip2 support not compiled in (enable `compress-bzip2`)")
+      }
+
+      #[cfg(feature = "compress-lzma")]
+      Compression::Xz => Ok(Box::new(xz2::write::XzEncoder::new(w, 6))),
+      #[cfg(not(feature = "compress-lzma"))]
+      Compression::Xz => {
+        bail!("xz support not compiled in (enable `compress-lzma`)")
+      }
+    }
+  }
+
+  /// Wrap a reader in the appropriate decoder. The result is not seekable, so
+  /// callers must skip entry content by consuming and discarding bytes.
+  pub fn wrap_reader<R: Read + 'static>(self, r: R) -> Result<Box<dyn Read>> {
+    match self {
+      Compression::None => Ok(Box::new(r)),
+
+      #[cfg(feature = "compress-gzip")]
+      Compression::Gzip => Ok(Box::new(flate2::read::GzDecoder::new(r))),
+      #[cfg(not(feature = "compress-gzip"))]
+      Compression::Gzip => {
+        bail!("gzip support not compiled in (enable `compress-gzip`)")
+      }
+
+      #[cfg(feature = "compress-zstd")]
+      Compression::Zstd => {
+        Ok(Box::new(zstd::stream::read::Decoder::new(r)?))
+      }
+      #[cfg(not(feature = "compress-zstd"))]
+      Compression::Zstd => {
+        bail!("zstd support not compiled in (enable `compress-zstd`)")
+      }
+
+      #[cfg(feature = "compress-bzip2")]
+      Compression::Bzip2 => Ok(Box::new(bzip2::read::BzDecoder::new(r))),
+      #[cfg(not(feature = "compress-bzip2"))]
+      Compression::Bzip2 => {
+        bail!("bzip2 support not compiled in (enable `compress-bzip2`)")
+      }
+
+      #[cfg(feature = "compress-lzma")]
+      Compression::Xz => Ok(Box::new(xz2::read::XzDecoder::new(r))),
+      #[cfg(not(feature = "compress-lzma"))]
+      Compression::Xz => {
+        bail!("xz support not compiled in (enable `compress-lzma`)")
+      }
+    }
+  }
+}
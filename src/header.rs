@@ -1,15 +1,20 @@
 use std::ffi::{CStr, CString, OsStr};
+use std::fs;
 use std::io::{Cursor, Read, Result as IoResult, Write};
 use std::os::unix::prelude::{MetadataExt, OsStrExt};
 use std::path::{Path, PathBuf};
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::time::{Duration, UNIX_EPOCH};
 
 use anyhow::{Context, Result};
 use bincode::Options;
 use nix::sys::stat;
 use nix::unistd::{Group, User};
 
-use crate::REGTYPE;
+use crate::{DIRTYPE, REGTYPE, SYMTYPE, XGLTYPE, XHDTYPE};
+
+/// A `size` of this value or larger no longer fits the 11-digit octal field
+/// and must be carried in a PAX extended header (or base-256, see below).
+const OCTAL_SIZE_LIMIT: u64 = 8u64.pow(11);
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Header {
@@ -28,14 +33,19 @@ pub struct Header {
   /// Size of file in bytes, 0-padded octal with null terminator
   pub size: u64,
 
-  /// File's modification time in Unix epoch time, 0-padded octal with null
-  /// terminator
-  pub mtime: SystemTime,
+  /// File's modification time as a `Duration` since the Unix epoch. Kept at
+  /// nanosecond resolution so a PAX fractional `mtime` round-trips; the ustar
+  /// field still only stores whole seconds.
+  pub mtime: Duration,
+
+  /// File's access time as a `Duration` since the Unix epoch. Only ever
+  /// carried in a PAX extended header, never in the fixed ustar block.
+  pub atime: Duration,
 
   /// File type (use constants defined below)
   pub typeflag: u8,
 
-  /// Unused for this project
+  /// Target of a symbolic or hard link, stored in the `linkname` field
   pub linkname: Option<PathBuf>,
 
   pub magic: [u8; 6],
@@ -62,15 +72,30 @@ impl Header {
   /// Create a Header for a given file path
   pub fn new(path: impl AsRef<Path>) -> Result<Self> {
     let path = path.as_ref();
-    let meta = path.metadata()?;
+    let meta = fs::symlink_metadata(path)?;
+    let file_type = meta.file_type();
+
+    // Directories and links carry no content; symlinks record their target in
+    // the `linkname` field. Hardlinks are detected by the caller, which sees
+    // repeated `(dev, ino)` pairs.
+    let (typeflag, size, linkname) = if file_type.is_dir() {
+      (DIRTYPE, 0, None)
+    } else if file_type.is_symlink() {
+      (SYMTYPE, 0, Some(fs::read_link(path)?))
+    } else {
+      (REGTYPE, meta.size(), None)
+    };
 
     let uid = meta.uid();
     let gid = meta.gid();
 
-    let user = User::from_uid(uid.into())?
-      .ok_or_else(|| anyhow!("no user with id {uid}"))?;
-    let group = Group::from_gid(gid.into())?
-      .ok_or_else(|| anyhow!("no group with id {gid}"))?;
+    // A uid/gid with no passwd/group entry (orphaned owner, numeric-UID
+    // container, ...) is routine when walking a real filesystem tree; fall
+    // back to an empty name instead of aborting the whole archive, as GNU
+    // tar does.
+    let uname = User::from_uid(uid.into())?.map(|u| u.name).unwrap_or_default();
+    let gname =
+      Group::from_gid(gid.into())?.map(|g| g.name).unwrap_or_default();
 
     let dev = meta.dev();
 
@@ -79,43 +104,141 @@ impl Header {
       mode: meta.mode(),
       uid,
       gid,
-      size: meta.size(),
-      mtime: meta.modified()?,
-      typeflag: REGTYPE,
-      linkname: None,
+      size,
+      mtime: meta
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default(),
+      atime: meta
+        .accessed()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .unwrap_or_default(),
+      typeflag,
+      linkname,
       magic: *b"ustar\0",
       version: *b"00",
-      uname: user.name,
-      gname: group.name,
+      uname,
+      gname,
       devmajor: stat::major(dev),
       devminor: stat::minor(dev),
       prefix: String::new(),
-      // atime: UNIX_EPOCH + Duration::from_secs(meta.atime() as u64),
-      // ctime: UNIX_EPOCH + Duration::from_secs(meta.ctime() as u64),
     })
   }
 
+  /// Construct the `typeflag == 'x'` PAX extended header that precedes a real
+  /// entry. Inherits the ownership/permission fields of its subject so the
+  /// extended block doesn't look out of place.
+  pub fn new_pax(subject: &Header, size: u64) -> Header {
+    Header {
+      name: PathBuf::from("@PaxHeader"),
+      mode: subject.mode,
+      uid: subject.uid,
+      gid: subject.gid,
+      size,
+      mtime: subject.mtime,
+      atime: Duration::default(),
+      typeflag: XHDTYPE,
+      linkname: None,
+      magic: *b"ustar\0",
+      version: *b"00",
+      uname: subject.uname.clone(),
+      gname: subject.gname.clone(),
+      devmajor: 0,
+      devminor: 0,
+      prefix: String::new(),
+    }
+  }
+
+  /// Collect the `<key>=<value>` overrides this header needs because one of
+  /// its fields overflows what the fixed ustar block can represent: a path
+  /// that is over 100 bytes and can't be split into `prefix`+`name`, a size
+  /// past the 11-digit octal limit, a too-long link target, or a fractional
+  /// `mtime`. `atime` has no ustar field to fall back to, so it is always
+  /// included.
+  pub fn pax_records(&self) -> Vec<(String, String)> {
+    let mut records = Vec::new();
+
+    let name_bytes = self.name.as_os_str().as_bytes();
+    if name_bytes.len() > 100 && split_ustar_name(&self.name).is_none() {
+      records.push(("path".to_owned(), self.name.display().to_string()));
+    }
+
+    if let Some(link) = &self.linkname {
+      if link.as_os_str().as_bytes().len() > 100 {
+        records.push(("linkpath".to_owned(), link.display().to_string()));
+      }
+    }
+
+    if self.size >= OCTAL_SIZE_LIMIT {
+      records.push(("size".to_owned(), self.size.to_string()));
+    }
+
+    if self.mtime.subsec_nanos() != 0 {
+      records.push(("mtime".to_owned(), format_pax_time(self.mtime)));
+    }
+
+    // Unlike `mtime`, `atime` has no ustar fallback field at all, so it must
+    // always go through a PAX record to survive a round-trip.
+    records.push(("atime".to_owned(), format_pax_time(self.atime)));
+
+    records
+  }
+
+  /// Encode a list of PAX records into the extended header payload.
+  pub fn encode_pax_records(records: &[(String, String)]) -> Vec<u8> {
+    let mut payload = Vec::new();
+    for (key, value) in records {
+      payload.extend_from_slice(pax_record(key, value).as_bytes());
+    }
+    payload
+  }
+
   /// Read a Header using any Read. If there's no more headers, returns None.
+  ///
+  /// PAX extended headers (`typeflag == 'x'`) and global extended headers
+  /// (`'g'`) are handled transparently: the `'x'` records are accumulated and
+  /// applied on top of the following real header, and `'g'` blocks are
+  /// skipped.
   pub fn read(mut r: impl Read) -> Result<Option<Self>> {
-    // Read exactly 512 bytes for the header
-    let mut buf = [0; 512];
-    r.read_exact(&mut buf)?;
-
-    // Fast zero check https://stackoverflow.com/a/65376133
-    let is_zero = {
-      let (prefix, aligned, suffix) = unsafe { buf.align_to::<u128>() };
-      prefix.iter().all(|&x| x == 0)
-        && suffix.iter().all(|&x| x == 0)
-        && aligned.iter().all(|&x| x == 0)
-    };
-    if is_zero {
-      return Ok(None);
+    let mut overrides = PaxExtensions::default();
+
+    loop {
+      // Read exactly 512 bytes for the header
+      let mut buf = [0; 512];
+      r.read_exact(&mut buf)?;
+
+      // Fast zero check https://stackoverflow.com/a/65376133
+      let is_zero = {
+        let (prefix, aligned, suffix) = unsafe { buf.align_to::<u128>() };
+        prefix.iter().all(|&x| x == 0)
+          && suffix.iter().all(|&x| x == 0)
+          && aligned.iter().all(|&x| x == 0)
+      };
+      if is_zero {
+        return Ok(None);
+      }
+
+      // Parse the header
+      let reader = HeaderReader::new(&buf);
+      let mut header = reader.read()?;
+
+      match header.typeflag {
+        XHDTYPE => {
+          let payload = read_content(&mut r, header.size)?;
+          overrides.absorb(&payload)?;
+        }
+        XGLTYPE => {
+          // A global header applies to every following entry; we don't model
+          // that, so simply skip its payload.
+          read_content(&mut r, header.size)?;
+        }
+        _ => {
+          overrides.apply(&mut header);
+          return Ok(Some(header));
+        }
+      }
     }
-
-    // Parse the header
-    let reader = HeaderReader::new(&buf);
-    let header = reader.read()?;
-    Ok(Some(header))
   }
 
   pub fn write(&self, w: impl Write) -> Result<()> {
@@ -144,6 +267,185 @@ impl Header {
   }
 }
 
+/// Split a path into the ustar `prefix`/`name` pair if it can be represented
+/// that way: the trailing `name` must be at most 100 bytes, the leading
+/// `prefix` at most 155, and the cut has to fall on a path separator. Returns
+/// `None` for paths that fit in 100 bytes (written directly) or that cannot be
+/// split at all (those need a PAX `path` record).
+fn split_ustar_name(path: &Path) -> Option<(String, String)> {
+  let bytes = path.as_os_str().as_bytes();
+  if bytes.len() <= 100 {
+    return None;
+  }
+
+  for (i, b) in bytes.iter().enumerate() {
+    if *b == b'/' && i <= 155 && bytes.len() - i - 1 <= 100 {
+      let prefix = std::str::from_utf8(&bytes[..i]).ok()?.to_owned();
+      let name = std::str::from_utf8(&bytes[i + 1..]).ok()?.to_owned();
+      return Some((prefix, name));
+    }
+  }
+
+  None
+}
+
+/// Format a `Duration` as a PAX timestamp, e.g. `1700000000.123456789`.
+fn format_pax_time(d: Duration) -> String {
+  format!("{}.{:09}", d.as_secs(), d.subsec_nanos())
+}
+
+/// Format one PAX record as `"<len> <key>=<value>\n"`, where `<len>` is the
+/// decimal byte length of the whole record including its own digits. The
+/// length is a fixpoint (adding a digit can push the total past a power of
+/// ten), so we iterate until it stabilises.
+fn pax_record(key: &str, value: &str) -> String {
+  let body = format!(" {}={}\n", key, value);
+  let mut len = body.len();
+  loop {
+    let candidate = len.to_string().len() + body.len();
+    if candidate == len {
+      break;
+    }
+    len = candidate;
+  }
+  format!("{}{}", len, body)
+}
+
+/// Parse a PAX timestamp (`<secs>[.<frac>]`) into a `Duration`.
+fn parse_pax_time(s: &str) -> Result<Duration> {
+  let mut parts = s.splitn(2, '.');
+  let secs: u64 = parts.next().unwrap_or("0").parse()?;
+  let nanos = match parts.next() {
+    Some(frac) => {
+      let mut frac = frac.to_owned();
+      frac.truncate(9);
+      while frac.len() < 9 {
+        frac.push('0');
+      }
+      frac.parse::<u32>()?
+    }
+    None => 0,
+  };
+  Ok(Duration::new(secs, nanos))
+}
+
+/// Overrides accumulated from one or more PAX extended headers, applied on top
+/// of the following real header (PAX wins).
+#[derive(Default)]
+struct PaxExtensions {
+  path: Option<PathBuf>,
+  linkpath: Option<PathBuf>,
+  size: Option<u64>,
+  uid: Option<u32>,
+  gid: Option<u32>,
+  mtime: Option<Duration>,
+  atime: Option<Duration>,
+}
+
+impl PaxExtensions {
+  /// Merge the records from a single extended-header payload.
+  fn absorb(&mut self, data: &[u8]) -> Result<()> {
+    let mut rest = data;
+    while !rest.is_empty() {
+      let sp = rest
+        .iter()
+        .position(|b| *b == b' ')
+        .context("malformed PAX record: no length")?;
+      let len: usize = std::str::from_utf8(&rest[..sp])?.parse()?;
+      ensure!(len > sp && len <= rest.len(), "bad PAX record length");
+
+      // Everything after the space up to (but not including) the trailing
+      // newline is "<key>=<value>".
+      let record = &rest[sp + 1..len];
+      let record = &record[..record.len().saturating_sub(1)];
+      let eq = record
+        .iter()
+        .position(|b| *b == b'=')
+        .context("PAX record missing '='")?;
+      let key = std::str::from_utf8(&record[..eq])?;
+      let value = std::str::from_utf8(&record[eq + 1..])?;
+
+      match key {
+        "path" => self.path = Some(PathBuf::from(value)),
+        "linkpath" => self.linkpath = Some(PathBuf::from(value)),
+        "size" => self.size = Some(value.parse()?),
+        "uid" => self.uid = Some(value.parse()?),
+        "gid" => self.gid = Some(value.parse()?),
+        "mtime" => self.mtime = Some(parse_pax_time(value)?),
+        "atime" => self.atime = Some(parse_pax_time(value)?),
+        _ => {}
+      }
+
+      rest = &rest[len..];
+    }
+    Ok(())
+  }
+
+  fn apply(&self, header: &mut Header) {
+    if let Some(path) = &self.path {
+      header.name = path.clone();
+    } else if !header.prefix.is_empty() {
+      // ustar split the name across `prefix`/`name`; rejoin it now that no
+      // PAX `path` record is overriding it (`TarFs::insert` in mount.rs
+      // relies on this).
+      header.name = Path::new(&header.prefix).join(&header.name);
+    }
+    if let Some(linkpath) = &self.linkpath {
+      header.linkname = Some(linkpath.clone());
+    }
+    if let Some(size) = self.size {
+      header.size = size;
+    }
+    if let Some(uid) = self.uid {
+      header.uid = uid;
+    }
+    if let Some(gid) = self.gid {
+      header.gid = gid;
+    }
+    if let Some(mtime) = self.mtime {
+      header.mtime = mtime;
+    }
+    if let Some(atime) = self.atime {
+      header.atime = atime;
+    }
+  }
+}
+
+/// Extract the octal text from a numeric field, stopping at the first NUL or
+/// space pad byte.
+fn octal_field_str(field: &[u8]) -> Result<String> {
+  let end = field
+    .iter()
+    .position(|b| *b == 0 || *b == b' ')
+    .unwrap_or(field.len());
+  Ok(std::str::from_utf8(&field[..end])?.to_owned())
+}
+
+/// Decode a GNU base-256 numeric field: the leading `0x80` flag byte marks the
+/// encoding, and the magnitude is stored big-endian in the remaining bytes.
+/// Only non-negative values are produced.
+fn read_base256(field: &[u8]) -> u64 {
+  let mut value = (field[0] & 0x7f) as u64;
+  for b in &field[1..] {
+    value = (value << 8) | *b as u64;
+  }
+  value
+}
+
+/// Read `size` bytes of entry content plus its 512-byte padding off a stream.
+fn read_content(r: &mut impl Read, size: u64) -> Result<Vec<u8>> {
+  let mut data = vec![0u8; size as usize];
+  r.read_exact(&mut data)?;
+
+  let padding = ((512 - (size % 512)) % 512) as usize;
+  if padding > 0 {
+    let mut pad = vec![0u8; padding];
+    r.read_exact(&mut pad)?;
+  }
+
+  Ok(data)
+}
+
 /// Helper struct for reading headers
 pub struct HeaderReader<'a> {
   pos: usize,
@@ -197,6 +499,7 @@ impl<'a> HeaderReader<'a> {
       gid,
       size,
       mtime,
+      atime: Duration::default(),
       typeflag,
       linkname,
       magic,
@@ -221,24 +524,31 @@ impl<'a> HeaderReader<'a> {
   }
 
   #[inline]
-  fn read_time(&mut self, len: usize) -> Result<SystemTime> {
+  fn read_time(&mut self, len: usize) -> Result<Duration> {
     let int = self.read_64(len, 8)?;
-    let duration = Duration::from_secs(int);
-    Ok(UNIX_EPOCH + duration)
+    Ok(Duration::from_secs(int))
   }
 
   #[inline]
   fn read_64(&mut self, len: usize, radix: u32) -> Result<u64> {
-    let string = self.read_string(len)?;
-    let int = u64::from_str_radix(&string, radix)?;
-    Ok(int)
+    let field = self.read_fixed(len)?;
+    if field[0] & 0x80 != 0 {
+      Ok(read_base256(field))
+    } else {
+      let int = u64::from_str_radix(&octal_field_str(field)?, radix)?;
+      Ok(int)
+    }
   }
 
   #[inline]
   fn read_octal_32(&mut self, len: usize) -> Result<u32> {
-    let string = self.read_string(len)?;
-    let int = u32::from_str_radix(&string, 8)?;
-    Ok(int)
+    let field = self.read_fixed(len)?;
+    if field[0] & 0x80 != 0 {
+      Ok(read_base256(field) as u32)
+    } else {
+      let int = u32::from_str_radix(&octal_field_str(field)?, 8)?;
+      Ok(int)
+    }
   }
 
   /// Turn a null-terminated string into a normal one
@@ -286,6 +596,20 @@ impl<'a> HeaderReader<'a> {
   }
 }
 
+/// Split a path into the `name` field bytes (<=100) and the `prefix` field for
+/// writing. Falls back to a 100-byte truncation for names that a PAX `path`
+/// record carries in full.
+fn split_name_for_ustar(path: &Path, existing_prefix: &str) -> (Vec<u8>, String) {
+  let bytes = path.as_os_str().as_bytes();
+  if bytes.len() <= 100 {
+    (bytes.to_vec(), existing_prefix.to_owned())
+  } else if let Some((prefix, name)) = split_ustar_name(path) {
+    (name.into_bytes(), prefix)
+  } else {
+    (bytes[..100].to_vec(), existing_prefix.to_owned())
+  }
+}
+
 /// Helper struct for writing headers
 pub struct HeaderWriter<'a, W: Write> {
   header: &'a Header,
@@ -295,8 +619,11 @@ pub struct HeaderWriter<'a, W: Write> {
 
 impl<'a, W: Write> HeaderWriter<'a, W> {
   pub fn write(mut self, write_checksum: bool) -> Result<()> {
+    let (name_field, prefix_field) =
+      split_name_for_ustar(&self.header.name, &self.header.prefix);
+
     self
-      .write_path(&self.header.name, 100)
+      .write_cstring(&name_field, 100)
       .context("could not write name")?;
 
     self
@@ -325,9 +652,19 @@ impl<'a, W: Write> HeaderWriter<'a, W> {
     let typeflag: [u8; 1] = [self.header.typeflag];
     self.inner_write(&typeflag)?;
 
-    let linkname = [0; 100];
-    self.inner_write(&linkname)?;
-    // let linkname = Some(self.read_path(100)?);
+    match &self.header.linkname {
+      Some(link) => {
+        let bytes = link.as_os_str().as_bytes();
+        // A target longer than 100 bytes is carried by a PAX `linkpath`
+        // record; truncate the fixed field in that case.
+        let field = if bytes.len() > 100 { &bytes[..100] } else { bytes };
+        self.write_cstring(field, 100)?;
+      }
+      None => {
+        let linkname = [0; 100];
+        self.inner_write(&linkname)?;
+      }
+    }
 
     self.inner_write(&self.header.magic)?;
     self.inner_write(&self.header.version)?;
@@ -345,7 +682,7 @@ impl<'a, W: Write> HeaderWriter<'a, W> {
     ensure!(devmajor.len() == 8);
     self.inner_write(&devminor)?;
 
-    self.write_cstring(&self.header.prefix, 155)?;
+    self.write_cstring(&prefix_field, 155)?;
 
     // 12 bytes off, so just write some padding
     let padding = [0; 12];
@@ -356,45 +693,59 @@ impl<'a, W: Write> HeaderWriter<'a, W> {
 
   #[inline]
   fn inner_write(&mut self, buf: &[u8]) -> IoResult<usize> {
-    /* println!(
-      "[{} .. {}] writing {} bytes: {:?}",
-      self.written,
-      self.written + buf.len(),
-      buf.len(),
-      std::str::from_utf8(buf)
-    ); */
     self.written += buf.len();
 
     self.w.write(buf)
   }
 
-  #[inline]
-  fn write_path(&mut self, path: impl AsRef<Path>, len: usize) -> Result<()> {
-    let path = path.as_ref();
-    let osstr = path.as_os_str();
-    let bytes = osstr.as_bytes();
-    self.write_cstring(bytes, len)
-  }
-
   #[inline]
   fn write_octal_32(&mut self, num: u32, len: usize) -> Result<()> {
     let s = format!("{:0width$o}", num, width = len - 1);
-    ensure!(s.len() == len - 1);
-    self.write_cstring(s, len)
+    if s.len() == len - 1 {
+      self.write_cstring(s, len)
+    } else {
+      self.write_base256(num as u64, len)
+    }
   }
 
   #[inline]
   fn write_octal_64(&mut self, num: u64, len: usize) -> Result<()> {
     let s = format!("{:0width$o}", num, width = len - 1);
-    ensure!(s.len() == len - 1);
-    self.write_cstring(s, len)
+    if s.len() == len - 1 {
+      self.write_cstring(s, len)
+    } else {
+      self.write_base256(num, len)
+    }
   }
 
+  /// Write a numeric field too large for its octal width using the GNU
+  /// base-256 extension: set the high bit of the first byte as a flag, then
+  /// store the magnitude big-endian in the remaining `len - 1` bytes.
   #[inline]
-  fn write_time(&mut self, time: SystemTime, len: usize) -> Result<()> {
-    let elapsed = time.duration_since(UNIX_EPOCH)?;
-    let secs = elapsed.as_secs();
-    self.write_octal_64(secs, len)
+  fn write_base256(&mut self, value: u64, len: usize) -> Result<()> {
+    let mut field = vec![0u8; len];
+    field[0] = 0x80;
+
+    let be = value.to_be_bytes();
+    let cap = len - 1;
+    if cap >= be.len() {
+      field[len - be.len()..].copy_from_slice(&be);
+    } else {
+      let extra = be.len() - cap;
+      ensure!(
+        be[..extra].iter().all(|b| *b == 0),
+        "value {value} too large for base-256 field of {len} bytes"
+      );
+      field[1..].copy_from_slice(&be[extra..]);
+    }
+
+    self.inner_write(&field)?;
+    Ok(())
+  }
+
+  #[inline]
+  fn write_time(&mut self, time: Duration, len: usize) -> Result<()> {
+    self.write_octal_64(time.as_secs(), len)
   }
 
   #[inline]
@@ -414,3 +765,97 @@ impl<'a, W: Write> HeaderWriter<'a, W> {
     Ok(())
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn split_ustar_name_fits_in_plain_name() {
+    // 100 bytes or fewer goes straight into `name`, no split needed.
+    assert_eq!(split_ustar_name(Path::new(&"a".repeat(100))), None);
+  }
+
+  #[test]
+  fn split_ustar_name_splits_on_separator() {
+    let path = format!("{}/{}", "a".repeat(90), "b".repeat(50));
+    let (prefix, name) = split_ustar_name(Path::new(&path)).unwrap();
+    assert_eq!(prefix, "a".repeat(90));
+    assert_eq!(name, "b".repeat(50));
+  }
+
+  #[test]
+  fn split_ustar_name_gives_up_when_no_separator_fits() {
+    // No `/` at all, so there's nowhere to cut; this has to fall back to a
+    // PAX `path` record instead.
+    assert_eq!(split_ustar_name(Path::new(&"a".repeat(200))), None);
+  }
+
+  #[test]
+  fn pax_time_round_trips_with_fraction() {
+    let d = Duration::new(1_700_000_000, 123_456_789);
+    assert_eq!(format_pax_time(d), "1700000000.123456789");
+    assert_eq!(parse_pax_time(&format_pax_time(d)).unwrap(), d);
+  }
+
+  #[test]
+  fn pax_time_parses_without_a_fraction() {
+    assert_eq!(
+      parse_pax_time("1700000000").unwrap(),
+      Duration::new(1_700_000_000, 0)
+    );
+  }
+
+  #[test]
+  fn pax_record_length_prefix_is_a_fixpoint() {
+    // " path=x\n" is 8 bytes; prefixing "9" pushes the total to 9, and "9"
+    // is still a single digit, so it settles there.
+    assert_eq!(pax_record("path", "x"), "9 path=x\n");
+  }
+
+  #[test]
+  fn pax_record_length_prefix_absorbs_its_own_digit_growth() {
+    // " path=" + 91 bytes + "\n" is a 98-byte body: a two-digit prefix puts
+    // the total at 100, which needs a third digit, so the length settles at
+    // 101.
+    let value = "x".repeat(91);
+    let record = pax_record("path", &value);
+    let sp = record.find(' ').unwrap();
+    let len: usize = record[..sp].parse().unwrap();
+    assert_eq!(len, record.len());
+  }
+
+  #[test]
+  fn base256_round_trips_through_write_and_read() {
+    let header = Header {
+      name: PathBuf::new(),
+      mode: 0,
+      uid: 0,
+      gid: 0,
+      size: 0,
+      mtime: Duration::ZERO,
+      atime: Duration::ZERO,
+      typeflag: REGTYPE,
+      linkname: None,
+      magic: *b"ustar\0",
+      version: *b"00",
+      uname: String::new(),
+      gname: String::new(),
+      devmajor: 0,
+      devminor: 0,
+      prefix: String::new(),
+    };
+
+    let mut buf = Vec::new();
+    let mut writer = HeaderWriter {
+      header: &header,
+      w: &mut buf,
+      written: 0,
+    };
+    let value = 8u64.pow(11); // one past the 11-digit octal limit
+    writer.write_base256(value, 12).unwrap();
+
+    assert_eq!(buf[0] & 0x80, 0x80);
+    assert_eq!(read_base256(&buf), value);
+  }
+}
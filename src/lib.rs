@@ -3,76 +3,213 @@ extern crate anyhow;
 #[macro_use]
 extern crate serde;
 
+pub mod archive;
+pub mod compression;
 pub mod header;
+pub mod mount;
 
-use std::collections::HashSet;
-use std::fs::{self, File, OpenOptions};
-use std::io::{Read, Seek, SeekFrom, Write};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions, Permissions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
 use std::iter;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result};
+use nix::sys::stat::{utimensat, UtimensatFlags};
+use nix::sys::time::TimeSpec;
+use nix::unistd::{chown, Gid, Uid};
 
+use crate::archive::Archive;
+use crate::compression::Compression;
 use crate::header::Header;
 
+/// Options controlling how metadata is restored on extraction.
+#[derive(Debug, Clone, Copy)]
+pub struct ExtractOptions {
+  /// Restore ownership (`uid`/`gid`), like tar's `--same-owner`; turning it
+  /// off skips `chown` entirely (`--no-same-owner`). When it is on, a failing
+  /// `chown` (e.g. for an unprivileged user) is ignored.
+  pub preserve: bool,
+}
+
+impl Default for ExtractOptions {
+  fn default() -> Self {
+    ExtractOptions { preserve: true }
+  }
+}
+
 const REGTYPE: u8 = b'0';
+/// Hard link to another entry named in `linkname`.
+const LNKTYPE: u8 = b'1';
+/// Symbolic link whose target is stored in `linkname`.
+const SYMTYPE: u8 = b'2';
+/// Directory entry, size 0.
+const DIRTYPE: u8 = b'5';
+/// PAX extended header, overriding the next entry's fields.
+const XHDTYPE: u8 = b'x';
+/// PAX global extended header (skipped on read).
+const XGLTYPE: u8 = b'g';
 const FOOTER_SIZE: usize = 1024;
 
-fn write_files_to_archive(
-  mut archive: &mut File,
+fn write_files_to_archive<W: Write>(
+  archive: &mut W,
   files: &[&str],
 ) -> Result<()> {
+  // Maps the first-seen `(dev, ino)` of a multiply-linked file to its path so
+  // later occurrences can be emitted as hardlinks.
+  let mut seen_inodes: HashMap<(u64, u64), PathBuf> = HashMap::new();
+
   for path in files {
-    let header: Header = Header::new(path)?;
-    header.write(&mut archive)?;
+    write_path_to_archive(archive, Path::new(path), &mut seen_inodes)?;
+  }
+
+  let footer_blocks = vec![0; FOOTER_SIZE];
+  archive.write_all(&footer_blocks)?;
+
+  Ok(())
+}
+
+/// Write a single path (recursing into directories) to the archive.
+fn write_path_to_archive<W: Write>(
+  archive: &mut W,
+  path: &Path,
+  seen_inodes: &mut HashMap<(u64, u64), PathBuf>,
+) -> Result<()> {
+  let meta = fs::symlink_metadata(path)?;
+  let mut header: Header = Header::new(path)?;
+
+  // A regular file with more than one link we've already archived becomes a
+  // hardlink entry pointing at its first occurrence.
+  if meta.file_type().is_file() && meta.nlink() > 1 {
+    let key = (meta.dev(), meta.ino());
+    if let Some(first) = seen_inodes.get(&key) {
+      header.typeflag = LNKTYPE;
+      header.linkname = Some(first.clone());
+      header.size = 0;
+      write_header_with_pax(archive, &header)?;
+      return Ok(());
+    }
+    seen_inodes.insert(key, path.to_path_buf());
+  }
+
+  write_header_with_pax(archive, &header)?;
+
+  match header.typeflag {
+    DIRTYPE => {
+      for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        write_path_to_archive(archive, &entry.path(), seen_inodes)?;
+      }
+    }
+    SYMTYPE | LNKTYPE => {
+      // Links carry no content.
+    }
+    _ => {
+      let source_bytes = fs::read(path)?;
+      archive.write_all(&source_bytes)?;
 
-    let source_bytes = fs::read(path)?;
-    archive.write_all(&source_bytes)?;
+      let file_size = header.size;
+      let padding_size = (512 - (file_size % 512) as usize) % 512;
+      if padding_size > 0 {
+        let padding: Vec<u8> = iter::repeat(0).take(padding_size).collect();
+        archive.write_all(&padding)?;
+      }
+    }
+  }
 
-    let file_size = header.size;
-    let padding_size = 512 - (file_size % 512) as usize;
+  Ok(())
+}
+
+/// Write a header, preceded by a PAX extended header carrying the records from
+/// `Header::pax_records`: any field that overflows the fixed ustar block, plus
+/// `atime`, which is always included, so every entry gets one.
+fn write_header_with_pax<W: Write>(
+  archive: &mut W,
+  header: &Header,
+) -> Result<()> {
+  let records = header.pax_records();
+  if !records.is_empty() {
+    let payload = Header::encode_pax_records(&records);
+    let pax = Header::new_pax(header, payload.len() as u64);
+    pax.write(&mut *archive)?;
+    archive.write_all(&payload)?;
+
+    let padding_size = (512 - (payload.len() % 512)) % 512;
     if padding_size > 0 {
       let padding: Vec<u8> = iter::repeat(0).take(padding_size).collect();
       archive.write_all(&padding)?;
     }
   }
 
-  let footer_blocks = vec![0; FOOTER_SIZE];
-  archive.write_all(&footer_blocks)?;
-
+  header.write(&mut *archive)?;
   Ok(())
 }
 
 pub fn create_archive(archive_name: &str, files: &[&str]) -> Result<()> {
-  let mut archive = File::create(archive_name)?;
+  let file = File::create(archive_name)?;
+  let mut archive = Compression::from_path(archive_name).wrap_writer(file)?;
   write_files_to_archive(&mut archive, files)?;
+  archive.flush()?;
   Ok(())
 }
 
+
 pub fn append_to_archive(archive_name: &str, files: &[&str]) -> Result<()> {
-  let mut archive = OpenOptions::new().append(true).open(archive_name)?;
-  archive.seek(SeekFrom::End(-(FOOTER_SIZE as i64)))?;
-  write_files_to_archive(&mut archive, files)?;
+  let mut probe = File::open(archive_name)?;
+  let compression = Compression::detect(&mut probe)?;
+  drop(probe);
+
+  if compression == Compression::None {
+    let mut archive = OpenOptions::new().write(true).open(archive_name)?;
+    archive.seek(SeekFrom::End(-(FOOTER_SIZE as i64)))?;
+    write_files_to_archive(&mut archive, files)?;
+    return Ok(());
+  }
+
+  // Compressed archives aren't seekable the way a raw ustar stream is, so
+  // splicing in place (as the uncompressed fast path does) would write
+  // uncompressed tar bytes into the middle of the compressed stream. Instead,
+  // decompress the whole archive, drop its footer, append the new entries,
+  // and recompress from scratch into a temporary file before replacing the
+  // original.
+  let compressed = fs::read(archive_name)?;
+  let mut decompressed = Vec::new();
+  compression
+    .wrap_reader(io::Cursor::new(compressed))?
+    .read_to_end(&mut decompressed)
+    .context("could not decompress existing archive")?;
+  ensure!(
+    decompressed.len() >= FOOTER_SIZE,
+    "archive is too short to contain a footer"
+  );
+  decompressed.truncate(decompressed.len() - FOOTER_SIZE);
+
+  let tmp_path = format!("{archive_name}.tmp");
+  let tmp_file = File::create(&tmp_path)?;
+  let mut writer = compression.wrap_writer(tmp_file)?;
+  writer.write_all(&decompressed)?;
+  write_files_to_archive(&mut writer, files)?;
+  writer.flush()?;
+  drop(writer);
+
+  fs::rename(&tmp_path, archive_name)?;
   Ok(())
 }
 
 pub fn get_archive_file_list(archive_name: &str) -> Result<Vec<String>> {
-  let mut archive = File::open(archive_name)?;
-  let mut file_names = Vec::new();
-
-  loop {
-    let header_opt =
-      Header::read(&mut archive).context("could not parse header")?;
-
-    let header: Header = match header_opt {
-      Some(v) => v,
-      None => break,
-    };
+  let mut file = File::open(archive_name)?;
+  let compression = Compression::detect(&mut file)?;
+  let reader = compression.wrap_reader(file)?;
 
-    file_names.push(header.name.display().to_string());
+  let mut archive = Archive::new(reader);
+  let mut file_names = Vec::new();
 
-    let num_content_blocks = (header.size as i64 + 511) / 512;
-    let content_bytes = num_content_blocks * 512;
-    archive.seek(SeekFrom::Current(content_bytes))?;
+  let mut entries = archive.entries();
+  while let Some(entry) = entries.next() {
+    let entry = entry.context("could not parse header")?;
+    file_names.push(entry.header.name.display().to_string());
+    // Dropping the entry skips its content, leaving us at the next header.
   }
 
   Ok(file_names)
@@ -90,33 +227,83 @@ pub fn update_archive(archive_name: &str, files: &[&str]) -> Result<()> {
   append_to_archive(archive_name, files)
 }
 
-pub fn extract_from_archive(archive_name: &str) -> Result<()> {
-  let mut archive = fs::File::open(archive_name)?;
-
-  loop {
-    let header = match Header::read(&mut archive)? {
-      Some(v) => v,
-      None => break,
-    };
-
-    let mut f = File::create(header.name)?;
-    let mut remaining_bytes = header.size as usize;
-    while remaining_bytes > 0 {
-      let chunk_size = if remaining_bytes >= 512 {
-        512_usize
-      } else {
-        remaining_bytes
-      };
-      let mut buf = vec![0u8; chunk_size];
-      archive.read_exact(&mut buf)?;
-      f.write_all(&buf)?;
-
-      remaining_bytes -= chunk_size;
+pub fn extract_from_archive(
+  archive_name: &str,
+  options: ExtractOptions,
+) -> Result<()> {
+  let mut file = fs::File::open(archive_name)?;
+  let compression = Compression::detect(&mut file)?;
+  let reader = compression.wrap_reader(file)?;
+
+  let mut archive = Archive::new(reader);
+  let mut entries = archive.entries();
+
+  while let Some(entry) = entries.next() {
+    let mut entry = entry?;
+
+    match entry.header.typeflag {
+      DIRTYPE => {
+        fs::create_dir_all(&entry.header.name)?;
+        restore_metadata(&entry.header.name, &entry.header, options)?;
+      }
+      SYMTYPE => {
+        let target = entry
+          .header
+          .linkname
+          .as_ref()
+          .context("symlink entry missing target")?;
+        let _ = fs::remove_file(&entry.header.name);
+        std::os::unix::fs::symlink(target, &entry.header.name)?;
+      }
+      LNKTYPE => {
+        let target = entry
+          .header
+          .linkname
+          .as_ref()
+          .context("hardlink entry missing target")?;
+        let _ = fs::remove_file(&entry.header.name);
+        fs::hard_link(target, &entry.header.name)?;
+      }
+      _ => {
+        let path = entry.header.name.clone();
+        let mut f = File::create(&path)?;
+        io::copy(&mut entry, &mut f)?;
+
+        drop(f);
+        restore_metadata(&path, &entry.header, options)?;
+      }
     }
+  }
 
-    let num_padding_bytes = 512 - (header.size % 512);
-    archive.seek(SeekFrom::Current(num_padding_bytes as i64))?;
+  Ok(())
+}
+
+/// Restore the permissions, ownership, and timestamps recorded in a header
+/// onto an already-created path. `chown` is best-effort: it needs privilege,
+/// and is skipped entirely unless `options.preserve` is set.
+fn restore_metadata(
+  path: &Path,
+  header: &Header,
+  options: ExtractOptions,
+) -> Result<()> {
+  // Ownership first: chown commonly clears setuid/setgid bits when it
+  // actually changes the owner, so chmod has to run after it (or the mode we
+  // just set could be silently stripped). Timestamps go last so nothing
+  // after them bumps atime/mtime again.
+  if options.preserve {
+    let uid = Some(Uid::from_raw(header.uid));
+    let gid = Some(Gid::from_raw(header.gid));
+    // Best-effort: unprivileged callers will get EPERM here.
+    let _ = chown(path, uid, gid);
   }
 
+  fs::set_permissions(path, Permissions::from_mode(header.mode))
+    .with_context(|| format!("could not set mode on {}", path.display()))?;
+
+  let atime = TimeSpec::from_duration(header.atime);
+  let mtime = TimeSpec::from_duration(header.mtime);
+  utimensat(None, path, &atime, &mtime, UtimensatFlags::FollowSymlink)
+    .with_context(|| format!("could not set times on {}", path.display()))?;
+
   Ok(())
 }
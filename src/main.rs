@@ -8,7 +8,16 @@ use anyhow::Result;
 fn main() -> Result<()> {
   let args: Vec<String> = env::args().collect();
   if args.len() < 4 {
-    bail!("Usage: {} -[c|a|t|x] -f <archive_name> <files>", &args[0]);
+    bail!(
+      "Usage: {} -[c|a|t|x] -f <archive_name> <files> | -m <archive> <mountpoint>",
+      &args[0]
+    );
+  }
+
+  // `-m <archive> <mountpoint>` uses a different argument layout from the
+  // `-X -f <archive> <files>` operations.
+  if args[1] == "-m" {
+    return minitar::mount::mount_archive(&args[2], &args[3]);
   }
 
   let archive_name = &args[3];
@@ -25,7 +34,9 @@ fn main() -> Result<()> {
       return Ok(());
     }
     "-u" => minitar::update_archive(archive_name, &file_names),
-    "-x" => minitar::extract_from_archive(archive_name),
+    "-x" => {
+      minitar::extract_from_archive(archive_name, Default::default())
+    }
     _ => {
       bail!("Unknown operation {}", &args[1]);
     }
@@ -0,0 +1,341 @@
+//! Read-only FUSE mount of a tar archive.
+//!
+//! Transparently decompresses the archive (see [`crate::compression`]) and
+//! scans every header once to build an in-memory index (content offset, size,
+//! mode, and typeflag of each entry), turning the `prefix`/`name` paths into a
+//! directory tree that's served through `fuser::Filesystem` without
+//! extracting anything to disk.
+
+use anyhow::Result;
+
+/// Mount `archive_name` read-only at `mountpoint`. Blocks until the filesystem
+/// is unmounted.
+#[cfg(feature = "mount")]
+pub fn mount_archive(archive_name: &str, mountpoint: &str) -> Result<()> {
+  use fuser::MountOption;
+
+  let fs = TarFs::new(archive_name)?;
+  let options = vec![
+    MountOption::RO,
+    MountOption::FSName("minitar".to_owned()),
+  ];
+  fuser::mount2(fs, mountpoint, &options)?;
+  Ok(())
+}
+
+#[cfg(not(feature = "mount"))]
+pub fn mount_archive(_archive_name: &str, _mountpoint: &str) -> Result<()> {
+  bail!("mount support not compiled in (enable the `mount` feature)")
+}
+
+#[cfg(feature = "mount")]
+use self::fs::TarFs;
+
+#[cfg(feature = "mount")]
+mod fs {
+  use std::collections::BTreeMap;
+  use std::ffi::{OsStr, OsString};
+  use std::fs::File;
+  use std::io::{Cursor, Read};
+  use std::os::unix::ffi::OsStrExt;
+  use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+  use anyhow::Result;
+  use fuser::{
+    FileAttr, FileType, Filesystem, ReplyAttr, ReplyData, ReplyDirectory,
+    ReplyEntry, Request,
+  };
+  use nix::libc::{EISDIR, ENOENT, ENOTDIR};
+
+  use crate::compression::Compression;
+  use crate::header::Header;
+
+  /// Attribute/entry cache lifetime handed back to the kernel.
+  const TTL: Duration = Duration::from_secs(1);
+
+  /// One node of the archive's directory tree. Inode numbers are indices into
+  /// `TarFs::inodes` (index 1 is the root; index 0 is unused).
+  struct Inode {
+    ino: u64,
+    parent: u64,
+    typeflag: u8,
+    mode: u32,
+    uid: u32,
+    gid: u32,
+    size: u64,
+    mtime: Duration,
+    /// Byte offset of this entry's content within the archive.
+    offset: u64,
+    /// Target of a symlink entry (`Header::linkname`), if any.
+    linkname: Option<OsString>,
+    children: BTreeMap<OsString, u64>,
+  }
+
+  pub struct TarFs {
+    /// The fully decompressed archive, kept in memory so entry content can be
+    /// served at arbitrary offsets: a transparently-decompressed reader
+    /// (see `Compression::wrap_reader`) isn't `Seek`, unlike a raw ustar
+    /// file.
+    content: Vec<u8>,
+    inodes: Vec<Inode>,
+  }
+
+  impl TarFs {
+    pub fn new(archive_name: &str) -> Result<Self> {
+      let mut file = File::open(archive_name)?;
+      let compression = Compression::detect(&mut file)?;
+      let mut reader = compression.wrap_reader(file)?;
+
+      let mut content = Vec::new();
+      reader.read_to_end(&mut content)?;
+
+      let mut inodes = vec![
+        // index 0: unused placeholder so ino numbering starts at 1.
+        Inode::dir(0, 0),
+        // index 1: root directory.
+        Inode::dir(1, 1),
+      ];
+
+      let mut cursor = Cursor::new(&content);
+      loop {
+        let header = match Header::read(&mut cursor)? {
+          Some(h) => h,
+          None => break,
+        };
+
+        let offset = cursor.position();
+        Self::insert(&mut inodes, &header, offset);
+
+        let num_content_blocks = (header.size + 511) / 512;
+        cursor.set_position(offset + num_content_blocks * 512);
+      }
+
+      Ok(TarFs { content, inodes })
+    }
+
+    /// Insert one header into the tree, creating intermediate directories.
+    fn insert(inodes: &mut Vec<Inode>, header: &Header, offset: u64) {
+      // `Header::read` already joins `prefix` into `name` (see
+      // `PaxExtensions::apply`), so `header.name` is the full path here.
+      let components: Vec<OsString> = header
+        .name
+        .components()
+        .filter_map(|c| match c {
+          std::path::Component::Normal(s) => Some(s.to_owned()),
+          _ => None,
+        })
+        .collect();
+      if components.is_empty() {
+        return;
+      }
+
+      let mut cur = 1u64;
+      for (i, comp) in components.iter().enumerate() {
+        let last = i == components.len() - 1;
+
+        if let Some(&child) = inodes[cur as usize].children.get(comp) {
+          cur = child;
+          if last {
+            // An explicit header for a path we'd only seen as an
+            // intermediate directory: fill in its real metadata.
+            let node = &mut inodes[child as usize];
+            node.typeflag = header.typeflag;
+            node.mode = header.mode;
+            node.uid = header.uid;
+            node.gid = header.gid;
+            node.size = header.size;
+            node.mtime = header.mtime;
+            node.offset = offset;
+            node.linkname =
+              header.linkname.as_ref().map(|l| l.as_os_str().to_owned());
+          }
+          continue;
+        }
+
+        let ino = inodes.len() as u64;
+        let node = if last {
+          Inode {
+            ino,
+            parent: cur,
+            typeflag: header.typeflag,
+            mode: header.mode,
+            uid: header.uid,
+            gid: header.gid,
+            size: header.size,
+            mtime: header.mtime,
+            offset,
+            linkname: header
+              .linkname
+              .as_ref()
+              .map(|l| l.as_os_str().to_owned()),
+            children: BTreeMap::new(),
+          }
+        } else {
+          Inode::dir(ino, cur)
+        };
+        inodes.push(node);
+        inodes[cur as usize].children.insert(comp.clone(), ino);
+        cur = ino;
+      }
+    }
+
+    fn attr(&self, inode: &Inode) -> FileAttr {
+      let kind = file_type(inode.typeflag);
+      let time = UNIX_EPOCH + inode.mtime;
+      FileAttr {
+        ino: inode.ino,
+        size: inode.size,
+        blocks: (inode.size + 511) / 512,
+        atime: time,
+        mtime: time,
+        ctime: time,
+        crtime: UNIX_EPOCH,
+        kind,
+        perm: (inode.mode & 0o7777) as u16,
+        nlink: if kind == FileType::Directory { 2 } else { 1 },
+        uid: inode.uid,
+        gid: inode.gid,
+        rdev: 0,
+        blksize: 512,
+        flags: 0,
+      }
+    }
+  }
+
+  impl Inode {
+    fn dir(ino: u64, parent: u64) -> Inode {
+      Inode {
+        ino,
+        parent,
+        typeflag: b'5',
+        mode: 0o755,
+        uid: 0,
+        gid: 0,
+        size: 0,
+        mtime: Duration::ZERO,
+        offset: 0,
+        linkname: None,
+        children: BTreeMap::new(),
+      }
+    }
+  }
+
+  fn file_type(typeflag: u8) -> FileType {
+    match typeflag {
+      b'5' => FileType::Directory,
+      b'2' => FileType::Symlink,
+      _ => FileType::RegularFile,
+    }
+  }
+
+  impl Filesystem for TarFs {
+    fn lookup(
+      &mut self,
+      _req: &Request,
+      parent: u64,
+      name: &OsStr,
+      reply: ReplyEntry,
+    ) {
+      let child = self
+        .inodes
+        .get(parent as usize)
+        .and_then(|p| p.children.get(name).copied());
+      match child {
+        Some(ino) => {
+          let attr = self.attr(&self.inodes[ino as usize]);
+          reply.entry(&TTL, &attr, 0);
+        }
+        None => reply.error(ENOENT),
+      }
+    }
+
+    fn getattr(&mut self, _req: &Request, ino: u64, reply: ReplyAttr) {
+      match self.inodes.get(ino as usize) {
+        Some(inode) => reply.attr(&TTL, &self.attr(inode)),
+        None => reply.error(ENOENT),
+      }
+    }
+
+    fn readdir(
+      &mut self,
+      _req: &Request,
+      ino: u64,
+      _fh: u64,
+      offset: i64,
+      mut reply: ReplyDirectory,
+    ) {
+      let inode = match self.inodes.get(ino as usize) {
+        Some(i) => i,
+        None => {
+          reply.error(ENOENT);
+          return;
+        }
+      };
+      if file_type(inode.typeflag) != FileType::Directory {
+        reply.error(ENOTDIR);
+        return;
+      }
+
+      let mut entries: Vec<(u64, FileType, OsString)> = vec![
+        (inode.ino, FileType::Directory, OsString::from(".")),
+        (inode.parent, FileType::Directory, OsString::from("..")),
+      ];
+      for (name, child) in &inode.children {
+        let kind = file_type(self.inodes[*child as usize].typeflag);
+        entries.push((*child, kind, name.clone()));
+      }
+
+      for (i, (ino, kind, name)) in
+        entries.into_iter().enumerate().skip(offset as usize)
+      {
+        if reply.add(ino, (i + 1) as i64, kind, &name) {
+          break;
+        }
+      }
+      reply.ok();
+    }
+
+    fn readlink(&mut self, _req: &Request, ino: u64, reply: ReplyData) {
+      match self.inodes.get(ino as usize).and_then(|i| i.linkname.as_ref()) {
+        Some(target) => reply.data(target.as_bytes()),
+        None => reply.error(ENOENT),
+      }
+    }
+
+    fn read(
+      &mut self,
+      _req: &Request,
+      ino: u64,
+      _fh: u64,
+      offset: i64,
+      size: u32,
+      _flags: i32,
+      _lock: Option<u64>,
+      reply: ReplyData,
+    ) {
+      let (content_offset, content_size, is_dir) =
+        match self.inodes.get(ino as usize) {
+          Some(i) => {
+            (i.offset, i.size, file_type(i.typeflag) == FileType::Directory)
+          }
+          None => {
+            reply.error(ENOENT);
+            return;
+          }
+        };
+      if is_dir {
+        reply.error(EISDIR);
+        return;
+      }
+
+      let start = content_offset + offset as u64;
+      let end = (content_offset + content_size).min(start + size as u64);
+      if start >= content_offset + content_size {
+        reply.data(&[]);
+        return;
+      }
+
+      reply.data(&self.content[start as usize..end as usize]);
+    }
+  }
+}